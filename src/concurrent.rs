@@ -0,0 +1,129 @@
+//! A sharded, thread-safe wrapper around [`LFUCache`] for use from
+//! multi-threaded servers, where the plain `&mut self`-on-`get` API would
+//! otherwise force callers onto a single global mutex.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::{CacheStats, LFUCache};
+
+/// Wraps `shard_count` independent [`LFUCache`] shards behind per-shard
+/// locks, selecting the shard by `hash(key) % shard_count`. Independent keys
+/// land in independent shards, so they don't contend on a single lock, and
+/// eviction stays local to whichever shard is under pressure rather than
+/// triggering a global stop-the-world purge.
+pub struct ConcurrentLFUCache<K: Hash + Eq, V> {
+    shards: Vec<Mutex<LFUCache<K, V>>>,
+}
+
+// SAFETY: `LFUCache` holds its keys in `Rc`s, which are neither `Send` nor
+// `Sync`. Every `Rc` a shard creates stays inside that shard (the methods
+// below hand out cloned values and counts, never keys), and a shard is only
+// touched while its mutex is held, so no two threads ever use the same
+// reference count at once.
+unsafe impl<K: Hash + Eq + Send, V: Send> Send for ConcurrentLFUCache<K, V> {}
+unsafe impl<K: Hash + Eq + Send, V: Send> Sync for ConcurrentLFUCache<K, V> {}
+
+impl<K: Hash + Eq, V> ConcurrentLFUCache<K, V> {
+    /// Splits `capacity` evenly across `shard_count` shards (each shard gets
+    /// at least 1 slot).
+    pub fn with_capacity(capacity: usize, shard_count: usize) -> ConcurrentLFUCache<K, V> {
+        let shard_count = shard_count.max(1);
+        let shard_capacity = (capacity / shard_count).max(1);
+        let shards = (0..shard_count)
+            .map(|_| Mutex::new(LFUCache::with_capacity(shard_capacity)))
+            .collect();
+        ConcurrentLFUCache { shards }
+    }
+
+    fn shard_index(&self, key: &K) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) % self.shards.len()
+    }
+
+    /// Returns a clone of the value associated with `key`, locking only the
+    /// shard that owns it.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        let idx = self.shard_index(key);
+        let mut shard = self.shards[idx].lock().unwrap();
+        shard.get(key).cloned()
+    }
+
+    pub fn set(&self, key: K, value: V) {
+        let idx = self.shard_index(&key);
+        let mut shard = self.shards[idx].lock().unwrap();
+        shard.set(key, value);
+    }
+
+    pub fn remove(&self, key: K) -> bool {
+        let idx = self.shard_index(&key);
+        let mut shard = self.shards[idx].lock().unwrap();
+        shard.remove(key)
+    }
+
+    /// Total number of entries resident across all shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Aggregates hit/miss/eviction counters across all shards.
+    pub fn stats(&self) -> CacheStats {
+        let mut hits = 0;
+        let mut misses = 0;
+        let mut evictions = 0;
+        for shard in &self.shards {
+            let shard_stats = shard.lock().unwrap().stats();
+            hits += shard_stats.hits;
+            misses += shard_stats.misses;
+            evictions += shard_stats.evictions;
+        }
+        let total = hits + misses;
+        let hit_ratio = if total == 0 { 0.0 } else { hits as f64 / total as f64 };
+        CacheStats {
+            hits,
+            misses,
+            evictions,
+            hit_ratio,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_round_trip_across_shards() {
+        // Generous per-shard headroom so uneven key-to-shard distribution
+        // doesn't evict anything before every key has been read back.
+        let cache = ConcurrentLFUCache::with_capacity(40, 4);
+        for k in 0..8 {
+            cache.set(k, k * 10);
+        }
+        for k in 0..8 {
+            assert_eq!(cache.get(&k), Some(k * 10));
+        }
+        assert_eq!(cache.len(), 8);
+        assert!(!cache.is_empty());
+    }
+
+    #[test]
+    fn stats_aggregate_across_shards() {
+        let cache = ConcurrentLFUCache::with_capacity(4, 2);
+        cache.set(1, 1);
+        cache.get(&1);
+        cache.get(&2);
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+    }
+}
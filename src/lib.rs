@@ -30,20 +30,52 @@ use std::prelude::v1::*;
 extern crate sgx_tstd as std;
 
 use std::collections::HashMap;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
+use std::collections::hash_map::RandomState;
 use linked_hash_set::LinkedHashSet;
 use std::rc::Rc;
 use std::fmt::Debug;
 use std::ops::Index;
 use std::collections::hash_map::{Iter, IntoIter};
 
+mod concurrent;
+mod s3fifo;
+mod sketch;
+
+pub use concurrent::ConcurrentLFUCache;
+use s3fifo::S3Fifo;
+use sketch::CountMinSketch;
 
 #[derive(Debug)]
-pub struct LFUCache<K: Hash + Eq, V> {
-    values: HashMap<Rc<K>, ValueCounter<V>>,
-    frequency_bin: HashMap<usize, LinkedHashSet<Rc<K>>>,
+pub struct LFUCache<K: Hash + Eq, V, S: BuildHasher = RandomState> {
+    values: HashMap<Rc<K>, ValueCounter<V>, S>,
+    frequency_bin: HashMap<usize, LinkedHashSet<Rc<K>>, S>,
     capacity: usize,
     min_frequency: usize,
+    /// W-TinyLFU admission filter, present only for caches created via
+    /// [`LFUCache::with_capacity_tiny_lfu`].
+    sketch: Option<CountMinSketch>,
+    /// S3-FIFO eviction state, present only for caches created via
+    /// [`LFUCache::with_capacity_s3fifo`]. When set, it fully replaces the
+    /// LFU bookkeeping above for `get`/`set`/`remove`/`len`/`contains`.
+    s3fifo: Option<S3Fifo<K, V>>,
+    /// Number of `get`/`set` operations between aging passes, set only for
+    /// caches created via [`LFUCache::with_capacity_and_decay`].
+    decay_after: Option<usize>,
+    ops_since_decay: usize,
+    hits: usize,
+    misses: usize,
+    evictions: usize,
+}
+
+/// Hit/miss/eviction counters, useful for A/B testing capacity or comparing
+/// eviction policies. See [`LFUCache::stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub hit_ratio: f64,
 }
 
 
@@ -63,27 +95,97 @@ impl<V> ValueCounter<V> {
 
 impl<K: Hash + Eq, V> LFUCache<K, V> {
     pub fn with_capacity(capacity: usize) -> LFUCache<K, V> {
+        LFUCache::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+
+    /// Like [`LFUCache::with_capacity`], but gates admission of new keys
+    /// through a W-TinyLFU frequency sketch: on a miss that would evict an
+    /// existing entry, the newcomer is only admitted if the sketch estimates
+    /// it as strictly more frequent than the eviction victim. This protects
+    /// the cache from one-hit-wonders churning out genuinely hot entries.
+    pub fn with_capacity_tiny_lfu(capacity: usize) -> LFUCache<K, V> {
+        let mut cache = LFUCache::with_capacity(capacity);
+        cache.sketch = Some(CountMinSketch::with_capacity(capacity));
+        cache
+    }
+
+    /// Like [`LFUCache::with_capacity`], but evicts using S3-FIFO instead of
+    /// LFU: a small FIFO admits newcomers, a main FIFO holds entries that
+    /// proved themselves with a second access, and a ghost queue remembers
+    /// evicted keys so they can skip straight back into the main queue. This
+    /// tends to beat LFU on scan-heavy and skewed workloads.
+    pub fn with_capacity_s3fifo(capacity: usize) -> LFUCache<K, V> {
+        let mut cache = LFUCache::with_capacity(capacity);
+        cache.s3fifo = Some(S3Fifo::with_capacity(capacity));
+        cache
+    }
+
+    /// Like [`LFUCache::with_capacity`], but every `decay_after` operations
+    /// halves every resident entry's frequency (see [`LFUCache::age`]). This
+    /// stops keys that were hammered early on and never touched again from
+    /// becoming effectively un-evictable.
+    pub fn with_capacity_and_decay(capacity: usize, decay_after: usize) -> LFUCache<K, V> {
+        let mut cache = LFUCache::with_capacity(capacity);
+        cache.decay_after = Some(decay_after);
+        cache
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> LFUCache<K, V, S> {
+    /// Creates an empty cache with the given capacity that hashes keys with
+    /// `hash_builder` instead of the default [`RandomState`]. Useful for
+    /// plugging in a faster or DoS-resistant hasher (e.g. `ahash`, `fnv`) on
+    /// a hot cache path, or a fixed seed for deterministic tests.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> LFUCache<K, V, S>
+    where
+        S: Clone,
+    {
         if capacity <= 0 {
             panic!("Unable to create cache: capacity is {:?}", capacity);
         }
         LFUCache {
-            values: HashMap::new(),
-            frequency_bin: HashMap::new(),
+            values: HashMap::with_hasher(hash_builder.clone()),
+            frequency_bin: HashMap::with_hasher(hash_builder),
             capacity,
             min_frequency: 0,
+            sketch: None,
+            s3fifo: None,
+            decay_after: None,
+            ops_since_decay: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
         }
     }
 
+    /// Alias of [`LFUCache::with_hasher`], matching the naming convention
+    /// used by `HashMap`/`hashlink`.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> LFUCache<K, V, S>
+    where
+        S: Clone,
+    {
+        LFUCache::with_hasher(capacity, hash_builder)
+    }
+
     pub fn contains(&self, key: &K) -> bool {
+        if let Some(s3fifo) = &self.s3fifo {
+            return s3fifo.contains(key);
+        }
         return self.values.contains_key(key);
     }
 
 
     pub fn len(&self) -> usize {
+        if let Some(s3fifo) = &self.s3fifo {
+            return s3fifo.len();
+        }
         self.values.len()
     }
 
     pub fn remove(&mut self, key: K) -> bool {
+        if let Some(s3fifo) = &mut self.s3fifo {
+            return s3fifo.remove(&key);
+        }
         let key = Rc::new(key);
         if let Some(value_counter) = self.values.get(&Rc::clone(&key)) {
             let count = value_counter.count;
@@ -96,17 +198,89 @@ impl<K: Hash + Eq, V> LFUCache<K, V> {
     /// Returns the value associated with the given key (if it still exists)
     /// Method marked as mutable because it internally updates the frequency of the accessed key
     pub fn get(&mut self, key: &K) -> Option<&V> {
-        let key = self.values.get_key_value(key).map(|(r, _)| Rc::clone(r))?;
+        if self.s3fifo.is_some() {
+            return self.get_s3fifo(key);
+        }
+        self.get_lfu(key)
+    }
+
+    fn get_s3fifo(&mut self, key: &K) -> Option<&V> {
+        let result = self.s3fifo.as_mut().unwrap().get(key);
+        if result.is_some() { self.hits += 1; } else { self.misses += 1; }
+        result
+    }
+
+    fn get_lfu(&mut self, key: &K) -> Option<&V> {
+        let key = match self.values.get_key_value(key).map(|(r, _)| Rc::clone(r)) {
+            Some(key) => key,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        self.hits += 1;
         self.update_frequency_bin(Rc::clone(&key));
+        if let Some(sketch) = &mut self.sketch {
+            sketch.increment(&*key);
+        }
+        self.maybe_age();
         self.values.get(&key).map(|x| &x.value)
     }
 
     pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        let key = self.values.get_key_value(key).map(|(r, _)| Rc::clone(r))?;
+        if self.s3fifo.is_some() {
+            return self.get_mut_s3fifo(key);
+        }
+        self.get_mut_lfu(key)
+    }
+
+    fn get_mut_s3fifo(&mut self, key: &K) -> Option<&mut V> {
+        let result = self.s3fifo.as_mut().unwrap().get_mut(key);
+        if result.is_some() { self.hits += 1; } else { self.misses += 1; }
+        result
+    }
+
+    fn get_mut_lfu(&mut self, key: &K) -> Option<&mut V> {
+        let key = match self.values.get_key_value(key).map(|(r, _)| Rc::clone(r)) {
+            Some(key) => key,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        self.hits += 1;
         self.update_frequency_bin(Rc::clone(&key));
+        if let Some(sketch) = &mut self.sketch {
+            sketch.increment(&*key);
+        }
+        self.maybe_age();
         self.values.get_mut(&key).map(|x| &mut x.value)
     }
 
+    /// Returns this cache's hit/miss/eviction counters since creation or the
+    /// last [`LFUCache::reset_stats`].
+    pub fn stats(&self) -> CacheStats {
+        let total = self.hits + self.misses;
+        let hit_ratio = if total == 0 { 0.0 } else { self.hits as f64 / total as f64 };
+        let evictions = self.evictions + self.s3fifo.as_ref().map_or(0, |s| s.evictions());
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions,
+            hit_ratio,
+        }
+    }
+
+    /// Zeroes out the hit/miss/eviction counters.
+    pub fn reset_stats(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+        self.evictions = 0;
+        if let Some(s3fifo) = &mut self.s3fifo {
+            s3fifo.reset_evictions();
+        }
+    }
+
 
     fn update_frequency_bin(&mut self, key: Rc<K>) {
         let value_counter = self.values.get_mut(&key).unwrap();
@@ -121,9 +295,56 @@ impl<K: Hash + Eq, V> LFUCache<K, V> {
     }
 
     pub fn evict(&mut self) {
+        if self.values.is_empty() {
+            return;
+        }
+        while self.frequency_bin.get(&self.min_frequency).is_none_or(|bin| bin.is_empty()) {
+            self.min_frequency += 1;
+        }
         let least_frequently_used_keys = self.frequency_bin.get_mut(&self.min_frequency).unwrap();
         let least_recently_used = least_frequently_used_keys.pop_front().unwrap();
         self.values.remove(&least_recently_used);
+        self.evictions += 1;
+    }
+
+    /// Halves every resident entry's frequency and rebuilds `frequency_bin`
+    /// accordingly, so keys that were hot early on and are now cold stop
+    /// crowding out genuinely active ones. Counts never decay below 1, so an
+    /// aged entry never falls below a brand-new one (which starts at 1).
+    pub fn age(&mut self) {
+        self.frequency_bin.clear();
+        let mut min_frequency = usize::MAX;
+        for value_counter in self.values.values_mut() {
+            value_counter.count = (value_counter.count / 2).max(1);
+            min_frequency = min_frequency.min(value_counter.count);
+        }
+        for (key, value_counter) in self.values.iter() {
+            self.frequency_bin.entry(value_counter.count).or_default().insert(Rc::clone(key));
+        }
+        self.min_frequency = if self.values.is_empty() { 0 } else { min_frequency };
+    }
+
+    fn maybe_age(&mut self) {
+        if let Some(decay_after) = self.decay_after {
+            self.ops_since_decay += 1;
+            if self.ops_since_decay >= decay_after {
+                self.age();
+                self.ops_since_decay = 0;
+            }
+        }
+    }
+
+    /// Changes the cache's capacity. If `new_capacity` is smaller than the
+    /// current length, entries are evicted until the cache fits.
+    pub fn set_capacity(&mut self, new_capacity: usize) {
+        self.capacity = new_capacity;
+        if let Some(s3fifo) = &mut self.s3fifo {
+            s3fifo.set_capacity(new_capacity);
+            return;
+        }
+        while self.len() > self.capacity {
+            self.evict();
+        }
     }
 
     pub fn iter(&self) -> LfuIterator<K, V> {
@@ -134,18 +355,39 @@ impl<K: Hash + Eq, V> LFUCache<K, V> {
 
 
     pub fn set(&mut self, key: K, value: V) {
+        if let Some(s3fifo) = &mut self.s3fifo {
+            return s3fifo.set(key, value);
+        }
         let key = Rc::new(key);
         if let Some(value_counter) = self.values.get_mut(&key) {
             value_counter.value = value;
             self.update_frequency_bin(Rc::clone(&key));
+            if let Some(sketch) = &mut self.sketch {
+                sketch.increment(&*key);
+            }
+            self.maybe_age();
             return;
         }
+        if let Some(sketch) = &mut self.sketch {
+            sketch.increment(&*key);
+        }
         if self.len() >= self.capacity {
+            if let Some(sketch) = &self.sketch {
+                let victim = self.frequency_bin.get(&self.min_frequency).and_then(|bin| bin.iter().next());
+                if let Some(victim) = victim {
+                    if sketch.estimate(&**victim) >= sketch.estimate(&*key) {
+                        // The newcomer isn't estimated hotter than the eviction
+                        // victim, so reject it rather than polluting the cache.
+                        return;
+                    }
+                }
+            }
             self.evict();
         }
         self.values.insert(Rc::clone(&key), ValueCounter { value, count: 1 });
         self.min_frequency = 1;
         self.frequency_bin.entry(self.min_frequency).or_default().insert(key);
+        self.maybe_age();
     }
 }
 
@@ -166,7 +408,7 @@ impl<K, V> Iterator for LfuConsumer<K, V> {
     }
 }
 
-impl<K: Eq + Hash, V> IntoIterator for LFUCache<K, V> {
+impl<K: Eq + Hash, V, S: BuildHasher> IntoIterator for LFUCache<K, V, S> {
     type Item = (Rc<K>, V);
     type IntoIter = LfuConsumer<K, V>;
 
@@ -183,7 +425,7 @@ impl<'a, K: Hash + Eq, V> Iterator for LfuIterator<'a, K, V> {
     }
 }
 
-impl<'a, K: Hash + Eq, V> IntoIterator for &'a LFUCache<K, V> {
+impl<'a, K: Hash + Eq, V, S: BuildHasher> IntoIterator for &'a LFUCache<K, V, S> {
     type Item = (Rc<K>, &'a V);
 
     type IntoIter = LfuIterator<'a, K, V>;
@@ -194,7 +436,7 @@ impl<'a, K: Hash + Eq, V> IntoIterator for &'a LFUCache<K, V> {
 }
 
 
-impl<K: Hash + Eq, V> Index<K> for LFUCache<K, V> {
+impl<K: Hash + Eq, V, S: BuildHasher> Index<K> for LFUCache<K, V, S> {
     type Output = V;
     fn index(&self, index: K) -> &Self::Output {
         return self.values.
@@ -293,4 +535,147 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_tiny_lfu_rejects_cold_newcomer() {
+        let mut lfu = LFUCache::with_capacity_tiny_lfu(2);
+        lfu.set(1, 1);
+        lfu.set(2, 2);
+        // Hammer both resident keys so the sketch estimates them as hot.
+        for _ in 0..5 {
+            lfu.get(&1);
+            lfu.get(&2);
+        }
+        // A single-touch newcomer shouldn't be estimated hotter than either
+        // resident entry, so it's rejected rather than evicting one of them.
+        lfu.set(3, 3);
+        assert_eq!(lfu.get(&1), Some(&1));
+        assert_eq!(lfu.get(&2), Some(&2));
+        assert_eq!(lfu.get(&3), None);
+    }
+
+    #[test]
+    fn test_set_capacity_shrinks_and_evicts() {
+        let mut lfu = LFUCache::with_capacity(3);
+        lfu.set(1, 1);
+        lfu.set(2, 2);
+        lfu.set(3, 3);
+        lfu.set_capacity(1);
+        assert_eq!(lfu.len(), 1);
+        assert_eq!(lfu.get(&3), Some(&3));
+    }
+
+    #[test]
+    fn test_evict_on_empty_cache_is_a_noop() {
+        let mut lfu: LFUCache<i32, i32> = LFUCache::with_capacity(2);
+        lfu.evict();
+        assert_eq!(lfu.len(), 0);
+    }
+
+    #[test]
+    fn test_with_hasher_uses_supplied_hash_builder() {
+        use std::cell::Cell;
+        use std::collections::hash_map::DefaultHasher;
+
+        // A `BuildHasher` that counts how many times it's asked to build a
+        // `Hasher`, so we can tell "the supplied hasher is actually threaded
+        // through" apart from "the argument is ignored and a fresh
+        // `RandomState` is built internally" — `RandomState::new()` can't
+        // distinguish those two cases since either way you just get a
+        // working hasher.
+        #[derive(Clone, Default)]
+        struct CountingBuildHasher {
+            calls: Rc<Cell<usize>>,
+        }
+
+        impl BuildHasher for CountingBuildHasher {
+            type Hasher = DefaultHasher;
+            fn build_hasher(&self) -> DefaultHasher {
+                self.calls.set(self.calls.get() + 1);
+                DefaultHasher::new()
+            }
+        }
+
+        let hasher = CountingBuildHasher::default();
+        assert_eq!(hasher.calls.get(), 0);
+        let mut lfu = LFUCache::with_hasher(2, hasher.clone());
+        lfu.set(1, 1);
+        assert!(hasher.calls.get() > 0);
+        assert_eq!(lfu.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn test_age_lets_cold_entry_evict_a_stale_hot_one() {
+        let mut lfu = LFUCache::with_capacity(2);
+        lfu.set(1, 1);
+        // Hammer `1` until its count (21) is far ahead of anything `2` will
+        // realistically accumulate, then age while `2` doesn't exist yet, so
+        // only `1`'s count gets halved (to 10).
+        for _ in 0..20 {
+            lfu.get(&1);
+        }
+        lfu.age();
+        lfu.set(2, 2);
+        // Give `2` more real accesses (count 15) than `1`'s post-age count
+        // (10) — without the earlier `age()` call, `1`'s count would still
+        // be 21 and comfortably ahead of `2`, so `2` would be evicted
+        // instead. The halving is what flips who loses eviction.
+        for _ in 0..14 {
+            lfu.get(&2);
+        }
+        lfu.evict();
+        assert_eq!(lfu.get(&1), None);
+        assert_eq!(lfu.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_with_capacity_and_decay_ages_automatically() {
+        // decay_after=21 matches the op count below exactly: `set` counts as
+        // op 1, then the 20th `get` pushes the running total to 21 and
+        // triggers an automatic `age()` — same scenario as
+        // `test_age_lets_cold_entry_evict_a_stale_hot_one`, but via the
+        // decay_after trigger instead of calling `age()` by hand.
+        let mut lfu = LFUCache::with_capacity_and_decay(2, 21);
+        lfu.set(1, 1);
+        for _ in 0..20 {
+            lfu.get(&1);
+        }
+        lfu.set(2, 2);
+        for _ in 0..14 {
+            lfu.get(&2);
+        }
+        lfu.evict();
+        // Without the automatic age(), `1`'s count (21) would still be far
+        // ahead of `2`'s (15), so `2` would be evicted instead.
+        assert_eq!(lfu.get(&1), None);
+        assert_eq!(lfu.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_stats_tracks_hits_misses_and_evictions() {
+        let mut lfu = LFUCache::with_capacity(1);
+        lfu.set(1, 1);
+        lfu.get(&1); // hit
+        lfu.get(&2); // miss
+        lfu.set(2, 2); // evicts 1
+        let stats = lfu.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.evictions, 1);
+        lfu.reset_stats();
+        let stats = lfu.stats();
+        assert_eq!(stats.hits, 0);
+        assert_eq!(stats.misses, 0);
+        assert_eq!(stats.evictions, 0);
+    }
+
+    #[test]
+    fn test_stats_counts_s3fifo_evictions_too() {
+        let mut lfu = LFUCache::with_capacity_s3fifo(1);
+        lfu.set(1, 1);
+        lfu.set(2, 2); // evicts 1 from the s3fifo-internal queues
+        assert_eq!(lfu.stats().evictions, 1);
+        lfu.reset_stats();
+        assert_eq!(lfu.stats().evictions, 0);
+    }
 }
@@ -0,0 +1,224 @@
+//! S3-FIFO: a three-queue FIFO eviction policy that gives much better hit
+//! ratios than plain LRU/LFU on scan-heavy and skewed workloads, without
+//! needing a heap or a recency list.
+//!
+//! Entries flow `small -> main` (promoted on a second access) or
+//! `small -> ghost -> main` (re-admitted after being evicted once), and each
+//! resident entry only needs a 2-bit access counter rather than an unbounded
+//! frequency.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Ceiling for the per-entry 2-bit access counter.
+const MAX_FREQ: u8 = 3;
+
+#[derive(Debug)]
+struct S3Entry<V> {
+    value: V,
+    freq: u8,
+}
+
+/// The S3-FIFO eviction policy: a small FIFO `S`, a main FIFO `M`, and a
+/// key-only ghost FIFO `G` that remembers recently evicted keys so they can
+/// be re-admitted straight into `M`.
+#[derive(Debug)]
+pub(crate) struct S3Fifo<K: Hash + Eq, V> {
+    small: VecDeque<Rc<K>>,
+    main: VecDeque<Rc<K>>,
+    ghost: VecDeque<Rc<K>>,
+    ghost_set: HashSet<Rc<K>>,
+    entries: HashMap<Rc<K>, S3Entry<V>>,
+    small_capacity: usize,
+    main_capacity: usize,
+    ghost_capacity: usize,
+    evictions: usize,
+}
+
+impl<K: Hash + Eq, V> S3Fifo<K, V> {
+    /// `small` holds ~10% of `capacity`, `main` the rest, and the key-only
+    /// ghost queue is sized like `main`. `small_capacity + main_capacity`
+    /// never exceeds `capacity`: a capacity of 1 goes entirely to `small`,
+    /// with `main` left empty rather than doubling the resident count.
+    pub(crate) fn with_capacity(capacity: usize) -> S3Fifo<K, V> {
+        let small_capacity = (capacity / 10).max(1).min(capacity.max(1));
+        let main_capacity = capacity.saturating_sub(small_capacity);
+        S3Fifo {
+            small: VecDeque::new(),
+            main: VecDeque::new(),
+            ghost: VecDeque::new(),
+            ghost_set: HashSet::new(),
+            entries: HashMap::new(),
+            small_capacity,
+            main_capacity,
+            ghost_capacity: main_capacity,
+            evictions: 0,
+        }
+    }
+
+    /// Resizes the small/main/ghost queues, evicting from `M` (and then `S`)
+    /// until the cache fits the new capacity.
+    pub(crate) fn set_capacity(&mut self, new_capacity: usize) {
+        self.small_capacity = (new_capacity / 10).max(1).min(new_capacity.max(1));
+        self.main_capacity = new_capacity.saturating_sub(self.small_capacity);
+        self.ghost_capacity = self.main_capacity;
+        self.evict_main();
+        self.evict_small();
+        self.evict_main();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub(crate) fn evictions(&self) -> usize {
+        self.evictions
+    }
+
+    pub(crate) fn reset_evictions(&mut self) {
+        self.evictions = 0;
+    }
+
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        let entry = self.entries.get_mut(key)?;
+        entry.freq = (entry.freq + 1).min(MAX_FREQ);
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let entry = self.entries.get_mut(key)?;
+        entry.freq = (entry.freq + 1).min(MAX_FREQ);
+        Some(&mut entry.value)
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> bool {
+        // A removed key may still be remembered in the ghost queue from an
+        // earlier eviction out of `S`; purge that ghost entry too so a later
+        // `set` for this key can't short-circuit straight into `M` via stale
+        // ghost state.
+        if self.ghost_set.remove(key) {
+            self.ghost.retain(|k| &**k != key);
+        }
+        if self.entries.remove(key).is_none() {
+            return false;
+        }
+        self.small.retain(|k| &**k != key);
+        self.main.retain(|k| &**k != key);
+        true
+    }
+
+    pub(crate) fn set(&mut self, key: K, value: V) {
+        let key = Rc::new(key);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.value = value;
+            entry.freq = (entry.freq + 1).min(MAX_FREQ);
+            return;
+        }
+        if self.ghost_set.remove(&key) {
+            self.ghost.retain(|k| *k != key);
+            self.main.push_back(Rc::clone(&key));
+            self.entries.insert(key, S3Entry { value, freq: 0 });
+            self.evict_main();
+        } else {
+            self.small.push_back(Rc::clone(&key));
+            self.entries.insert(key, S3Entry { value, freq: 0 });
+            self.evict_small();
+        }
+    }
+
+    /// Pops the head of `S`: entries that were accessed again migrate to `M`,
+    /// the rest are evicted and remembered in the ghost queue.
+    fn evict_small(&mut self) {
+        while self.small.len() > self.small_capacity {
+            let head = self.small.pop_front().unwrap();
+            let freq = self.entries.get(&head).map(|entry| entry.freq).unwrap_or(0);
+            if freq > 0 {
+                if let Some(entry) = self.entries.get_mut(&head) {
+                    entry.freq = 0;
+                }
+                self.main.push_back(head);
+                self.evict_main();
+            } else {
+                self.entries.remove(&head);
+                self.push_ghost(head);
+                self.evictions += 1;
+            }
+        }
+    }
+
+    /// Pops the head of `M`: entries with remaining accesses get a second
+    /// chance (decremented counter, pushed to the tail), the rest are
+    /// evicted outright.
+    fn evict_main(&mut self) {
+        while self.main.len() > self.main_capacity {
+            let head = self.main.pop_front().unwrap();
+            let freq = self.entries.get(&head).map(|entry| entry.freq).unwrap_or(0);
+            if freq > 0 {
+                if let Some(entry) = self.entries.get_mut(&head) {
+                    entry.freq -= 1;
+                }
+                self.main.push_back(head);
+            } else {
+                self.entries.remove(&head);
+                self.evictions += 1;
+            }
+        }
+    }
+
+    fn push_ghost(&mut self, key: Rc<K>) {
+        if self.ghost_set.insert(Rc::clone(&key)) {
+            self.ghost.push_back(key);
+            while self.ghost.len() > self.ghost_capacity {
+                if let Some(old) = self.ghost.pop_front() {
+                    self.ghost_set.remove(&old);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_one_holds_a_single_entry() {
+        let mut s3 = S3Fifo::with_capacity(1);
+        s3.set(1, 1);
+        s3.set(2, 2);
+        assert_eq!(s3.len(), 1);
+    }
+
+    #[test]
+    fn remove_purges_stale_ghost_entry() {
+        // Capacity 3 gives `small` 1 slot and the ghost queue 2.
+        let mut s3 = S3Fifo::with_capacity(3);
+        s3.set(1, 1);
+        s3.set(2, 2); // evicts 1 from `small` into the ghost queue
+        s3.set(3, 3); // evicts 2 into the ghost queue, which still holds 1
+        assert!(!s3.contains(&1));
+        s3.remove(&1);
+        // If the ghost entry for `1` weren't purged, re-inserting it here
+        // would skip straight into `main` instead of starting cold in `small`,
+        // where it pushes `3` out.
+        s3.set(1, 10);
+        assert_eq!(s3.get(&1), Some(&10));
+        assert!(!s3.contains(&3));
+    }
+
+    #[test]
+    fn second_access_promotes_to_main_and_survives_small_eviction() {
+        let mut s3 = S3Fifo::with_capacity(10);
+        s3.set(1, 1);
+        s3.get(&1); // second access promotes 1 into `main` on eviction
+        for k in 2..20 {
+            s3.set(k, k);
+        }
+        assert_eq!(s3.get(&1), Some(&1));
+    }
+}
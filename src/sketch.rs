@@ -0,0 +1,77 @@
+//! A Count-Min Sketch frequency estimator used to implement W-TinyLFU admission.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Number of independent hash functions used per lookup/update.
+const DEPTH: usize = 4;
+
+/// Saturating ceiling for each 4-bit counter.
+const MAX_COUNT: u8 = 15;
+
+/// A fixed-size Count-Min Sketch with periodic aging, as used by W-TinyLFU to
+/// cheaply approximate access frequency without keeping a counter per key.
+#[derive(Debug)]
+pub(crate) struct CountMinSketch {
+    table: Vec<Vec<u8>>,
+    width: usize,
+    size: usize,
+    sample_size: usize,
+}
+
+impl CountMinSketch {
+    /// Sizes the sketch off of the cache capacity: `width` is rounded up to a
+    /// power of two, and the sketch halves its counters (ages) every
+    /// `sample_size` (~10x capacity) increments.
+    pub(crate) fn with_capacity(capacity: usize) -> CountMinSketch {
+        let width = capacity.max(1).next_power_of_two();
+        CountMinSketch {
+            table: vec![vec![0u8; width]; DEPTH],
+            width,
+            size: 0,
+            sample_size: capacity.max(1) * 10,
+        }
+    }
+
+    fn index<K: Hash>(&self, key: &K, row: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) & (self.width - 1)
+    }
+
+    /// Returns the estimated frequency of `key`: the minimum of its `DEPTH`
+    /// addressed counters.
+    pub(crate) fn estimate<K: Hash>(&self, key: &K) -> u8 {
+        (0..DEPTH)
+            .map(|row| self.table[row][self.index(key, row)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Bumps every counter addressed by `key`, saturating at `MAX_COUNT`, and
+    /// ages the whole sketch once `sample_size` increments have accumulated.
+    pub(crate) fn increment<K: Hash>(&mut self, key: &K) {
+        for row in 0..DEPTH {
+            let idx = self.index(key, row);
+            let counter = &mut self.table[row][idx];
+            if *counter < MAX_COUNT {
+                *counter += 1;
+            }
+        }
+        self.size += 1;
+        if self.size >= self.sample_size {
+            self.age();
+        }
+    }
+
+    /// Halves every counter and the running size, so stale frequencies decay.
+    fn age(&mut self) {
+        for row in self.table.iter_mut() {
+            for counter in row.iter_mut() {
+                *counter >>= 1;
+            }
+        }
+        self.size >>= 1;
+    }
+}